@@ -1,11 +1,124 @@
-use rusqlite::{Connection, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result};
 
-pub struct Database;
+/// One hit from [`Database::search`].
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub item_id: String,
+    pub snippet: String,
+}
+
+pub struct Database {
+    conn: Connection,
+}
 
 impl Database {
-    pub fn new() -> Result<Connection> {
-        Connection::open("epub_editor.db")
+    pub fn new() -> Result<Self> {
+        let conn = Connection::open("epub_editor.db")?;
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS chapters USING fts5(source_path UNINDEXED, item_id, body);
+             CREATE TABLE IF NOT EXISTS indexed_books (
+                 source_path TEXT PRIMARY KEY,
+                 mtime INTEGER NOT NULL
+             );",
+        )?;
+        Ok(Self { conn })
+    }
+
+    /// Indexes `chapters` (id, plain-text body) for full-text search, keyed
+    /// by `source_path` so multiple books can share the same index without
+    /// stepping on each other. Skips the work entirely if `mtime` matches
+    /// what was indexed last time, so reopening an unchanged book is a
+    /// no-op.
+    pub fn index_book(&self, source_path: &str, mtime: i64, chapters: &[(String, String)]) -> Result<()> {
+        let last_mtime: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT mtime FROM indexed_books WHERE source_path = ?1",
+                params![source_path],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if last_mtime == Some(mtime) {
+            return Ok(());
+        }
+
+        self.conn
+            .execute("DELETE FROM chapters WHERE source_path = ?1", params![source_path])?;
+        for (item_id, body) in chapters {
+            self.conn.execute(
+                "INSERT INTO chapters (source_path, item_id, body) VALUES (?1, ?2, ?3)",
+                params![source_path, item_id, body],
+            )?;
+        }
+        self.conn.execute(
+            "INSERT INTO indexed_books (source_path, mtime) VALUES (?1, ?2)
+             ON CONFLICT(source_path) DO UPDATE SET mtime = excluded.mtime",
+            params![source_path, mtime],
+        )?;
+        Ok(())
+    }
+
+    /// Runs an FTS5 `MATCH` query against the chapters indexed for `source_path`.
+    pub fn search(&self, source_path: &str, query: &str) -> Result<Vec<SearchResult>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT item_id, snippet(chapters, 2, '', '', '...', 10)
+             FROM chapters WHERE chapters MATCH ?1 AND source_path = ?2 ORDER BY rank",
+        )?;
+        let rows = stmt.query_map(params![query, source_path], |row| {
+            Ok(SearchResult {
+                item_id: row.get(0)?,
+                snippet: row.get(1)?,
+            })
+        })?;
+        rows.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_db() -> Database {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE chapters USING fts5(source_path UNINDEXED, item_id, body);
+             CREATE TABLE indexed_books (source_path TEXT PRIMARY KEY, mtime INTEGER NOT NULL);",
+        )
+        .unwrap();
+        Database { conn }
     }
 
-    // 数据库操作方法
+    #[test]
+    fn search_is_scoped_to_the_requesting_book() {
+        let db = memory_db();
+        db.index_book("a.epub", 1, &[("ch1".into(), "the quick fox".into())])
+            .unwrap();
+        db.index_book("b.epub", 1, &[("ch1".into(), "the quick fox".into())])
+            .unwrap();
+
+        // Reindexing "a.epub" at the same mtime is a no-op, but its rows
+        // must still be there because indexing "b.epub" replaced only its own rows.
+        db.index_book("a.epub", 1, &[("ch1".into(), "the quick fox".into())])
+            .unwrap();
+
+        let hits_a = db.search("a.epub", "quick").unwrap();
+        let hits_b = db.search("b.epub", "quick").unwrap();
+        assert_eq!(hits_a.len(), 1);
+        assert_eq!(hits_b.len(), 1);
+    }
+
+    #[test]
+    fn reindexing_a_changed_book_replaces_only_its_own_rows() {
+        let db = memory_db();
+        db.index_book("a.epub", 1, &[("ch1".into(), "apples".into())])
+            .unwrap();
+        db.index_book("b.epub", 1, &[("ch1".into(), "bananas".into())])
+            .unwrap();
+        db.index_book("a.epub", 2, &[("ch1".into(), "oranges".into())])
+            .unwrap();
+
+        assert!(db.search("a.epub", "apples").unwrap().is_empty());
+        assert_eq!(db.search("a.epub", "oranges").unwrap().len(), 1);
+        assert_eq!(db.search("b.epub", "bananas").unwrap().len(), 1);
+    }
 }
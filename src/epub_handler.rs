@@ -1,5 +1,59 @@
-use epub::doc::EpubDoc;
-use std::path::Path;
+use epub::doc::{EpubDoc, NavPoint};
+use epub_builder::{EpubBuilder, EpubContent, ReferenceType, ZipLibrary};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::text_extract::{self, Heading};
+use crate::toc::{self, ChapterOutline, TocEntry};
+
+/// In-memory representation of an opened EPUB, built from its OPF manifest and spine.
+#[derive(Debug, Clone)]
+pub struct Epub {
+    pub metadata: EpubMetadata,
+    pub spine: Vec<String>,
+    pub manifest: HashMap<String, EpubItem>,
+    /// Table of contents, from the book's own nav document when it has one,
+    /// synthesized from chapter headings otherwise.
+    pub toc: Vec<TocEntry>,
+    /// Path the book was opened from, used to key the search index.
+    pub source_path: Option<PathBuf>,
+}
+
+impl Epub {
+    pub fn new() -> Self {
+        Self {
+            metadata: EpubMetadata::default(),
+            spine: Vec::new(),
+            manifest: HashMap::new(),
+            toc: Vec::new(),
+            source_path: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EpubMetadata {
+    pub title: String,
+    pub author: String,
+    pub language: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct EpubItem {
+    pub id: String,
+    pub href: String,
+    pub media_type: String,
+    pub content: String,
+    /// Raw bytes as stored in the EPUB archive. Kept alongside `content` so
+    /// binary resources (images, fonts) survive an edit/export round-trip
+    /// even though only textual items get a decoded `content`.
+    pub data: Vec<u8>,
+    /// Cleaned body text and heading outline, extracted from `content` for
+    /// (X)HTML items. Empty for binary resources.
+    pub plain_text: String,
+    pub outline: Vec<Heading>,
+}
 
 pub struct EpubHandler;
 
@@ -8,19 +62,399 @@ impl EpubHandler {
         Self
     }
 
-    pub fn open_epub<P: AsRef<Path>>(path: P) -> Result<String, String> {
+    /// Parses an EPUB into its spine/manifest model.
+    ///
+    /// `EpubDoc::new` resolves `META-INF/container.xml` to find the rootfile
+    /// OPF and already parses its manifest and spine `itemref`s, so we only
+    /// need to walk the fields it exposes: `metadata` for the book's
+    /// title/author/language, `spine` for the reading order, and
+    /// `resources` for each manifest item's id/href/media type. Textual
+    /// (X)HTML items also get their decompressed content pulled in so the
+    /// editor has something to show as soon as a chapter is selected.
+    pub fn open_epub<P: AsRef<Path>>(path: P) -> Result<Epub, String> {
         println!("[INFO] 打开 epub");
-        match EpubDoc::new(path) {
-            Ok(doc) => {
-                let content = doc
-                    .metadata
-                    .iter()
-                    .map(|(k, v)| format!("{}: {}", k, v.join(", ")))
-                    .collect::<Vec<String>>()
-                    .join("\n");
-                Ok(content)
+        let path = path.as_ref();
+        let mut doc = EpubDoc::new(path).map_err(|e| format!("Error opening EPUB: {}", e))?;
+
+        let metadata = EpubMetadata {
+            title: doc.mdata("title").unwrap_or_default(),
+            author: doc.mdata("creator").unwrap_or_default(),
+            language: doc.mdata("language").unwrap_or_default(),
+        };
+
+        let spine = doc.spine.clone();
+
+        let ids: Vec<String> = doc.resources.keys().cloned().collect();
+        let mut manifest = HashMap::with_capacity(ids.len());
+        for id in ids {
+            let (href, media_type) = match doc.resources.get(&id) {
+                Some((path, mime)) => (path.to_string_lossy().into_owned(), mime.clone()),
+                None => continue,
+            };
+
+            // Decode the resource once: textual items are decompressed as a
+            // string and `data` is derived from it, binary items are
+            // decompressed as bytes directly.
+            let is_text = is_text_media_type(&media_type);
+            let content = if is_text {
+                doc.get_resource_str(&id)
+                    .map(|(text, _)| text)
+                    .unwrap_or_default()
+            } else {
+                String::new()
+            };
+            let binary_data = if is_text {
+                Vec::new()
+            } else {
+                doc.get_resource(&id)
+                    .map(|(bytes, _)| bytes)
+                    .unwrap_or_default()
+            };
+
+            manifest.insert(id.clone(), build_item(id, href, media_type, content, binary_data));
+        }
+
+        let toc = if doc.toc.is_empty() {
+            let chapter_headings: Vec<(String, String, Vec<(u8, String)>)> = spine
+                .iter()
+                .filter_map(|id| manifest.get(id))
+                .map(|item| {
+                    let headings = item.outline.iter().map(|h| (h.level, h.text.clone())).collect();
+                    (item.id.clone(), item.href.clone(), headings)
+                })
+                .collect();
+            let outlines: Vec<ChapterOutline> = chapter_headings
+                .iter()
+                .map(|(item_id, href, headings)| ChapterOutline {
+                    item_id,
+                    href,
+                    headings,
+                })
+                .collect();
+            toc::from_headings(&spine, &outlines)
+        } else {
+            convert_nav_points(&doc.toc, &manifest, "t", 1)
+        };
+
+        Ok(Epub {
+            metadata,
+            spine,
+            manifest,
+            toc,
+            source_path: Some(path.to_owned()),
+        })
+    }
+
+    /// Rebuilds `epub` into a valid EPUB file at `out` using `epub-builder`.
+    ///
+    /// Spine items are pushed as XHTML content in reading order, each with a
+    /// real chapter title (from [`epub.toc`](Epub::toc) or its outline) so
+    /// `epub-builder`'s generated nav/NCX has working, labeled entries.
+    /// Everything else in the manifest (images including SVG, stylesheets,
+    /// fonts, ...) is copied over verbatim as a resource, except (X)HTML items
+    /// outside the spine and the NCX, which are not copied. That covers the
+    /// book's own original `nav.xhtml`/`toc.ncx`, left out since `epub-builder` generates its own from the content
+    /// added above and re-adding the stale original would either collide
+    /// with it or resurrect pre-edit navigation. `epub-builder` takes care
+    /// of emitting the mimetype file and `META-INF/container.xml`.
+    pub fn write_epub(epub: &Epub, out: &Path) -> Result<(), String> {
+        let zip = ZipLibrary::new().map_err(|e| e.to_string())?;
+        let mut builder = EpubBuilder::new(zip).map_err(|e| e.to_string())?;
+
+        builder
+            .metadata("title", &epub.metadata.title)
+            .map_err(|e| e.to_string())?;
+        builder
+            .metadata("author", &epub.metadata.author)
+            .map_err(|e| e.to_string())?;
+        builder
+            .metadata("lang", &epub.metadata.language)
+            .map_err(|e| e.to_string())?;
+
+        for id in &epub.spine {
+            let Some(item) = epub.manifest.get(id) else {
+                continue;
+            };
+            builder
+                .add_content(
+                    EpubContent::new(item.href.clone(), item.content.as_bytes())
+                        .title(chapter_title_for(epub, id))
+                        .reftype(ReferenceType::Text),
+                )
+                .map_err(|e| e.to_string())?;
+        }
+
+        for item in epub.manifest.values() {
+            let is_nav_candidate = item.media_type.contains("html")
+                || item.media_type == "application/x-dtbncx+xml";
+            if epub.spine.contains(&item.id) || is_nav_candidate {
+                continue;
+            }
+            builder
+                .add_resource(
+                    item.href.clone(),
+                    item.data.as_slice(),
+                    item.media_type.clone(),
+                )
+                .map_err(|e| e.to_string())?;
+        }
+
+        let mut out_file = File::create(out).map_err(|e| e.to_string())?;
+        builder
+            .generate(&mut out_file)
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Writes a single chapter's current XHTML out as a standalone file, for
+    /// per-chapter export (as opposed to [`EpubHandler::write_epub`], which
+    /// rebuilds the whole book).
+    pub fn write_chapter(item: &EpubItem, out: &Path) -> Result<(), String> {
+        std::fs::write(out, item.content.as_bytes()).map_err(|e| e.to_string())
+    }
+}
+
+/// Whether `media_type` is (X)HTML/XML rather than a binary resource.
+///
+/// Decides whether a manifest item is read as a string and gets
+/// plain-text/outline extraction, or is kept as raw bytes.
+fn is_text_media_type(media_type: &str) -> bool {
+    media_type.contains("html") || media_type.contains("xml")
+}
+
+/// Builds a manifest `EpubItem` from an already-decoded resource. Textual
+/// items derive `data` from `content` instead of decoding the resource a
+/// second time, and get `plain_text`/`outline` extracted; binary items keep
+/// `binary_data` as-is and have no extractable text.
+fn build_item(
+    id: String,
+    href: String,
+    media_type: String,
+    content: String,
+    binary_data: Vec<u8>,
+) -> EpubItem {
+    let is_text = is_text_media_type(&media_type);
+    let data = if is_text {
+        content.clone().into_bytes()
+    } else {
+        binary_data
+    };
+    let (plain_text, outline) = if is_text {
+        let extracted = text_extract::extract(&content);
+        (extracted.text, extracted.headings)
+    } else {
+        (String::new(), Vec::new())
+    };
+
+    EpubItem {
+        id,
+        href,
+        media_type,
+        content,
+        data,
+        plain_text,
+        outline,
+    }
+}
+
+/// Picks a chapter's display title for the exported nav/NCX: the label of
+/// its top-level `epub.toc` entry if it has one, else its first heading,
+/// else the manifest id.
+fn chapter_title_for(epub: &Epub, id: &str) -> String {
+    epub.toc
+        .iter()
+        .find(|entry| entry.target_id.as_deref() == Some(id))
+        .map(|entry| entry.label.clone())
+        .or_else(|| {
+            epub.manifest
+                .get(id)
+                .and_then(|item| item.outline.first())
+                .map(|heading| heading.text.clone())
+        })
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// Converts `EpubDoc`'s already-parsed nav tree (EPUB3 `nav.xhtml` or the
+/// EPUB2 `toc.ncx` `navMap`, whichever the book ships) into our `TocEntry`
+/// tree, resolving each entry's href back to a manifest id.
+fn convert_nav_points(
+    points: &[NavPoint],
+    manifest: &HashMap<String, EpubItem>,
+    key_prefix: &str,
+    level: u8,
+) -> Vec<TocEntry> {
+    points
+        .iter()
+        .enumerate()
+        .map(|(i, point)| {
+            let href = point.content.to_string_lossy().into_owned();
+            let key = format!("{key_prefix}.{i}");
+            TocEntry {
+                target_id: resolve_href_to_id(&href, manifest),
+                key: key.clone(),
+                label: point.label.clone(),
+                href,
+                level,
+                children: convert_nav_points(&point.children, manifest, &key, level + 1),
             }
-            Err(e) => Err(format!("Error opening EPUB: {}", e)),
+        })
+        .collect()
+}
+
+/// Matches a nav entry's href (possibly with a `#fragment`) against the
+/// manifest item whose href it targets.
+///
+/// Compares full normalized paths rather than a loose suffix match: two
+/// distinct resources can share a path suffix (`text/ch1.xhtml` vs
+/// `images/ch1.xhtml`, or `ch1.xhtml` vs `prech1.xhtml`), and a suffix match
+/// over `manifest`'s `HashMap` would pick whichever happened to come first in
+/// hash order.
+fn resolve_href_to_id(href: &str, manifest: &HashMap<String, EpubItem>) -> Option<String> {
+    let target = normalize_href(href.split('#').next().unwrap_or(href));
+    manifest
+        .values()
+        .find(|item| normalize_href(&item.href) == target)
+        .map(|item| item.id.clone())
+}
+
+/// Normalizes a manifest-relative path so the same resource compares equal
+/// regardless of a leading `"./"` or backslash separators.
+fn normalize_href(path: &str) -> String {
+    path.trim_start_matches("./").replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, href: &str) -> EpubItem {
+        EpubItem {
+            id: id.to_string(),
+            href: href.to_string(),
+            media_type: "application/xhtml+xml".to_string(),
+            content: String::new(),
+            data: Vec::new(),
+            plain_text: String::new(),
+            outline: Vec::new(),
         }
     }
+
+    #[test]
+    fn resolve_href_to_id_does_not_confuse_hrefs_sharing_a_suffix() {
+        let mut manifest = HashMap::new();
+        manifest.insert("text-ch1".to_string(), item("text-ch1", "text/ch1.xhtml"));
+        manifest.insert("img-ch1".to_string(), item("img-ch1", "images/ch1.xhtml"));
+
+        assert_eq!(
+            resolve_href_to_id("text/ch1.xhtml", &manifest),
+            Some("text-ch1".to_string())
+        );
+        assert_eq!(
+            resolve_href_to_id("images/ch1.xhtml", &manifest),
+            Some("img-ch1".to_string())
+        );
+    }
+
+    #[test]
+    fn resolve_href_to_id_ignores_fragment_and_leading_dot_slash() {
+        let mut manifest = HashMap::new();
+        manifest.insert("ch1".to_string(), item("ch1", "ch1.xhtml"));
+
+        assert_eq!(
+            resolve_href_to_id("./ch1.xhtml#section-2", &manifest),
+            Some("ch1".to_string())
+        );
+    }
+
+    #[test]
+    fn resolve_href_to_id_does_not_loose_match_on_filename_substring() {
+        let mut manifest = HashMap::new();
+        manifest.insert("ch1".to_string(), item("ch1", "ch1.xhtml"));
+        manifest.insert("prech1".to_string(), item("prech1", "prech1.xhtml"));
+
+        assert_eq!(
+            resolve_href_to_id("prech1.xhtml", &manifest),
+            Some("prech1".to_string())
+        );
+    }
+
+    #[test]
+    fn is_text_media_type_covers_xhtml_and_ncx_but_not_binary_resources() {
+        assert!(is_text_media_type("application/xhtml+xml"));
+        assert!(is_text_media_type("application/x-dtbncx+xml"));
+        assert!(!is_text_media_type("text/css"));
+        assert!(!is_text_media_type("image/jpeg"));
+    }
+
+    #[test]
+    fn build_item_derives_data_from_content_for_text_items_without_redecoding() {
+        let item = build_item(
+            "ch1".to_string(),
+            "ch1.xhtml".to_string(),
+            "application/xhtml+xml".to_string(),
+            "<h1>Title</h1><p>Body</p>".to_string(),
+            Vec::new(),
+        );
+        assert_eq!(item.data, item.content.clone().into_bytes());
+        assert_eq!(item.outline.len(), 1);
+        assert_eq!(item.outline[0].text, "Title");
+    }
+
+    #[test]
+    fn build_item_keeps_binary_data_as_is_and_extracts_nothing() {
+        let item = build_item(
+            "cover".to_string(),
+            "cover.jpg".to_string(),
+            "image/jpeg".to_string(),
+            String::new(),
+            vec![0xFF, 0xD8, 0xFF],
+        );
+        assert_eq!(item.data, vec![0xFF, 0xD8, 0xFF]);
+        assert!(item.plain_text.is_empty());
+        assert!(item.outline.is_empty());
+    }
+
+    #[test]
+    fn chapter_title_for_prefers_toc_label_over_outline_and_id() {
+        let mut manifest = HashMap::new();
+        manifest.insert("ch1".to_string(), item("ch1", "ch1.xhtml"));
+        let epub = Epub {
+            metadata: EpubMetadata::default(),
+            spine: vec!["ch1".to_string()],
+            manifest,
+            toc: vec![TocEntry {
+                key: "0".to_string(),
+                label: "Chapter One".to_string(),
+                href: "ch1.xhtml".to_string(),
+                target_id: Some("ch1".to_string()),
+                level: 1,
+                children: Vec::new(),
+            }],
+            source_path: None,
+        };
+
+        assert_eq!(chapter_title_for(&epub, "ch1"), "Chapter One");
+    }
+
+    #[test]
+    fn chapter_title_for_falls_back_to_outline_then_id() {
+        let mut with_outline = item("ch1", "ch1.xhtml");
+        with_outline.outline = vec![Heading {
+            level: 1,
+            text: "Heading Title".to_string(),
+        }];
+        let mut manifest = HashMap::new();
+        manifest.insert("ch1".to_string(), with_outline);
+        manifest.insert("ch2".to_string(), item("ch2", "ch2.xhtml"));
+        let epub = Epub {
+            metadata: EpubMetadata::default(),
+            spine: vec!["ch1".to_string(), "ch2".to_string()],
+            manifest,
+            toc: Vec::new(),
+            source_path: None,
+        };
+
+        assert_eq!(chapter_title_for(&epub, "ch1"), "Heading Title");
+        assert_eq!(chapter_title_for(&epub, "ch2"), "ch2");
+    }
 }
@@ -0,0 +1,96 @@
+use crate::epub_handler::EpubMetadata;
+
+/// Used whenever the user's template is empty or resolves to nothing usable.
+pub const DEFAULT_TEMPLATE: &str = "{name} - {author}.epub";
+
+/// Per-chapter bindings available in addition to the book's metadata.
+#[derive(Debug, Clone, Default)]
+pub struct ChapterBindings<'a> {
+    pub index: usize,
+    pub chapter_title: &'a str,
+}
+
+/// Resolves an export filename template against `metadata` and, for
+/// per-chapter export, `chapter`. Recognizes `{name}`, `{author}`,
+/// `{language}`, `{index}`, and `{chapterTitle}`; anything else is left as
+/// literal text. Falls back to [`DEFAULT_TEMPLATE`] when `template` is blank
+/// or resolves to an empty filename.
+pub fn resolve(template: &str, metadata: &EpubMetadata, chapter: &ChapterBindings) -> String {
+    let effective = if template.trim().is_empty() {
+        DEFAULT_TEMPLATE
+    } else {
+        template
+    };
+    let resolved = sanitize(expand(effective, metadata, chapter).trim());
+
+    if resolved.is_empty() {
+        sanitize(&expand(DEFAULT_TEMPLATE, metadata, chapter))
+    } else {
+        resolved
+    }
+}
+
+fn expand(template: &str, metadata: &EpubMetadata, chapter: &ChapterBindings) -> String {
+    template
+        .replace("{name}", &metadata.title)
+        .replace("{author}", &metadata.author)
+        .replace("{language}", &metadata.language)
+        .replace("{index}", &chapter.index.to_string())
+        .replace("{chapterTitle}", chapter.chapter_title)
+}
+
+/// Replaces characters illegal in filenames on common filesystems.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> EpubMetadata {
+        EpubMetadata {
+            title: "My Book".to_string(),
+            author: "Jane Doe".to_string(),
+            language: "en".to_string(),
+        }
+    }
+
+    #[test]
+    fn expands_book_and_chapter_bindings() {
+        let chapter = ChapterBindings {
+            index: 3,
+            chapter_title: "Intro",
+        };
+        let resolved = resolve("{index} - {chapterTitle} ({name})", &metadata(), &chapter);
+        assert_eq!(resolved, "3 - Intro (My Book)");
+    }
+
+    #[test]
+    fn falls_back_to_default_template_when_blank() {
+        let resolved = resolve("", &metadata(), &ChapterBindings::default());
+        assert_eq!(resolved, "My Book - Jane Doe.epub");
+    }
+
+    #[test]
+    fn falls_back_to_default_template_when_result_is_empty() {
+        // Not blank, but `{chapterTitle}` is empty in the default bindings.
+        let resolved = resolve("{chapterTitle}", &metadata(), &ChapterBindings::default());
+        assert_eq!(resolved, "My Book - Jane Doe.epub");
+    }
+
+    #[test]
+    fn sanitizes_illegal_path_characters() {
+        let chapter = ChapterBindings {
+            index: 1,
+            chapter_title: "What? A Twist: Part One",
+        };
+        let resolved = resolve("{chapterTitle}.epub", &metadata(), &chapter);
+        assert_eq!(resolved, "What_ A Twist_ Part One.epub");
+    }
+}
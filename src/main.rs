@@ -1,4 +1,8 @@
+mod db;
 mod epub_handler;
+mod export_template;
+mod text_extract;
+mod toc;
 mod ui;
 
 use iced::{Application, Settings};
@@ -0,0 +1,252 @@
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+/// Subtrees whose text never belongs in the editable body.
+const SKIP_TAGS: &[&str] = &["script", "style", "nav", "svg", "iframe"];
+
+/// Tags that start a new block of text, so paragraphs don't run together.
+const PARAGRAPH_TAGS: &[&str] = &["p", "div", "li", "blockquote", "section", "article", "br"];
+
+/// One `<h1>`-`<h6>` found while walking a chapter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+}
+
+/// Readable text pulled out of a chapter, plus its heading outline.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractedContent {
+    pub text: String,
+    pub headings: Vec<Heading>,
+}
+
+/// Strips `xhtml` down to readable text and records its heading outline.
+///
+/// Walks the document as a stream of tags rather than building a DOM, since
+/// chapters only need to be read once. `<script>`, `<style>`, `<nav>`,
+/// `<svg>`, and `<iframe>` subtrees are skipped entirely, runs of whitespace
+/// collapse to a single space, and `&nbsp;`/CDATA are decoded to the
+/// characters they represent rather than left as markup.
+///
+/// `<h1>`-`<h6>` are kept in the flattened text as ATX-style Markdown
+/// headings (`"## Heading"`) rather than dropped, separated from surrounding
+/// paragraphs by a blank line. That's the mapping [`reserialize`] reads back
+/// to turn an edited chapter's headings into real `<hN>` tags again instead
+/// of losing them to a flat `<p>`.
+pub fn extract(xhtml: &str) -> ExtractedContent {
+    let mut reader = Reader::from_str(xhtml);
+    reader.config_mut().trim_text(true);
+
+    let mut headings = Vec::new();
+    let mut blocks: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut skip_depth: u32 = 0;
+    let mut heading_level: Option<u8> = None;
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Eof) => break,
+            Ok(Event::Start(e)) => {
+                let name = tag_name(e.name().as_ref());
+                if SKIP_TAGS.contains(&name.as_str()) {
+                    skip_depth += 1;
+                } else if skip_depth == 0 {
+                    if let Some(level) = heading_level_of(&name) {
+                        flush_block(&mut current, &mut blocks, None);
+                        heading_level = Some(level);
+                    } else if PARAGRAPH_TAGS.contains(&name.as_str()) {
+                        flush_block(&mut current, &mut blocks, heading_level);
+                    }
+                }
+            }
+            Ok(Event::Empty(e)) => {
+                let name = tag_name(e.name().as_ref());
+                if skip_depth == 0 && PARAGRAPH_TAGS.contains(&name.as_str()) {
+                    flush_block(&mut current, &mut blocks, heading_level);
+                }
+            }
+            Ok(Event::End(e)) => {
+                let name = tag_name(e.name().as_ref());
+                if SKIP_TAGS.contains(&name.as_str()) {
+                    skip_depth = skip_depth.saturating_sub(1);
+                } else if skip_depth == 0 {
+                    if heading_level.is_some() && heading_level == heading_level_of(&name) {
+                        if let Some(level) = heading_level {
+                            headings.push(Heading {
+                                level,
+                                text: collapse_whitespace(&current),
+                            });
+                        }
+                        flush_block(&mut current, &mut blocks, heading_level);
+                        heading_level = None;
+                    } else if PARAGRAPH_TAGS.contains(&name.as_str()) {
+                        flush_block(&mut current, &mut blocks, heading_level);
+                    }
+                }
+            }
+            Ok(Event::Text(e)) if skip_depth == 0 => {
+                append_inline(&decode_entities(e.as_ref()), &mut current);
+            }
+            Ok(Event::CData(e)) if skip_depth == 0 => {
+                // CDATA is already literal characters, not markup to unescape.
+                append_inline(&String::from_utf8_lossy(e.as_ref()), &mut current);
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+        buf.clear();
+    }
+    flush_block(&mut current, &mut blocks, heading_level);
+
+    ExtractedContent {
+        text: blocks.join("\n\n"),
+        headings,
+    }
+}
+
+/// Rebuilds a minimal XHTML body from edited plain text.
+///
+/// Each blank-line-separated block is saved back as a `<p>`, except a block
+/// that starts with `"#"`-`"######"` followed by a space, which came from
+/// [`extract`]'s Markdown-style heading marker and round-trips back into an
+/// `<hN>` tag. This is still a simplification (inline markup like emphasis
+/// or links doesn't survive the edit), but headings specifically are
+/// preserved rather than flattened.
+pub fn reserialize(plain_text: &str) -> String {
+    let body = plain_text
+        .split("\n\n")
+        .filter_map(|block| {
+            let block = block.trim();
+            if block.is_empty() {
+                return None;
+            }
+            Some(match parse_heading_marker(block) {
+                Some((level, text)) => format!("<h{level}>{}</h{level}>", escape(text)),
+                None => format!("<p>{}</p>", escape(block)),
+            })
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\"><body>\n{body}\n</body></html>"
+    )
+}
+
+fn tag_name(qualified: &[u8]) -> String {
+    let raw = String::from_utf8_lossy(qualified).into_owned();
+    raw.rsplit(':').next().unwrap_or(&raw).to_lowercase()
+}
+
+fn heading_level_of(name: &str) -> Option<u8> {
+    match name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+fn parse_heading_marker(block: &str) -> Option<(u8, &str)> {
+    let hashes = block.chars().take_while(|&c| c == '#').count();
+    if hashes == 0 || hashes > 6 {
+        return None;
+    }
+    let rest = block[hashes..].strip_prefix(' ')?;
+    (!rest.is_empty()).then_some((hashes as u8, rest))
+}
+
+fn decode_entities(bytes: &[u8]) -> String {
+    let raw = String::from_utf8_lossy(bytes);
+    quick_xml::escape::unescape_with(&raw, |entity| match entity {
+        "nbsp" => Some("\u{A0}"),
+        _ => None,
+    })
+    .map(|s| s.into_owned())
+    .unwrap_or_else(|_| raw.into_owned())
+}
+
+fn append_inline(decoded: &str, buffer: &mut String) {
+    if !buffer.is_empty() && !buffer.ends_with(' ') {
+        buffer.push(' ');
+    }
+    buffer.push_str(decoded.trim());
+}
+
+/// Flushes the in-progress block into `blocks`, tagging it as a Markdown
+/// heading marker when `heading_level` is set. No-op if empty.
+fn flush_block(current: &mut String, blocks: &mut Vec<String>, heading_level: Option<u8>) {
+    let collapsed = collapse_whitespace(current);
+    current.clear();
+    if collapsed.is_empty() {
+        return;
+    }
+    match heading_level {
+        Some(level) => blocks.push(format!("{} {collapsed}", "#".repeat(level as usize))),
+        None => blocks.push(collapsed),
+    }
+}
+
+fn collapse_whitespace(s: &str) -> String {
+    // U+00A0 (from `&nbsp;`) counts as Unicode whitespace but is kept as is.
+    s.split(|c: char| c.is_whitespace() && c != '\u{A0}')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_headings_and_body_text() {
+        let xhtml = "<html><body><h1>Title</h1><p>Hello <b>world</b></p></body></html>";
+        let extracted = extract(xhtml);
+        assert_eq!(extracted.headings, vec![Heading { level: 1, text: "Title".into() }]);
+        assert_eq!(extracted.text, "# Title\n\nHello world");
+    }
+
+    #[test]
+    fn skips_script_style_nav_svg_iframe_subtrees() {
+        let xhtml = "<body><script>alert(1)</script><style>.a{}</style>\
+                     <nav>links</nav><svg><text>shape label</text></svg>\
+                     <iframe>embedded</iframe><p>kept</p></body>";
+        let extracted = extract(xhtml);
+        assert_eq!(extracted.text, "kept");
+    }
+
+    #[test]
+    fn decodes_nbsp_and_cdata() {
+        let xhtml = "<p>a&nbsp;b</p><p><![CDATA[raw <tag>]]></p>";
+        let extracted = extract(xhtml);
+        assert!(extracted.text.contains("a\u{A0}b"));
+        assert!(extracted.text.contains("raw <tag>"));
+    }
+
+    #[test]
+    fn reserialize_round_trips_headings() {
+        let original = "<html><body><h2>Chapter One</h2><p>Body text</p></body></html>";
+        let extracted = extract(original);
+        let rebuilt = reserialize(&extracted.text);
+        let reextracted = extract(&rebuilt);
+        assert_eq!(reextracted.headings, extracted.headings);
+        assert_eq!(reextracted.text, extracted.text);
+    }
+
+    #[test]
+    fn reserialize_treats_plain_blocks_as_paragraphs() {
+        let xhtml = reserialize("just a paragraph");
+        assert!(xhtml.contains("<p>just a paragraph</p>"));
+    }
+}
@@ -0,0 +1,143 @@
+/// One entry in a book's table of contents: a label, the manifest item it
+/// points to, and its nested sub-entries.
+#[derive(Debug, Clone)]
+pub struct TocEntry {
+    /// Stable, unique key (a dotted position path) used to track
+    /// collapse/expand state in the UI.
+    pub key: String,
+    pub label: String,
+    pub href: String,
+    pub target_id: Option<String>,
+    pub level: u8,
+    pub children: Vec<TocEntry>,
+}
+
+/// A chapter's outline, as needed to synthesize a TOC from it.
+pub struct ChapterOutline<'a> {
+    pub item_id: &'a str,
+    pub href: &'a str,
+    pub headings: &'a [(u8, String)],
+}
+
+/// Builds a TOC straight from each chapter's heading outline, for books that
+/// ship no `nav.xhtml`/`toc.ncx` of their own. A chapter with no headings
+/// becomes one top-level entry labeled by its manifest id; otherwise its
+/// `<h1>`-`<h6>` become the entries themselves, nested by level, so a
+/// chapter's outermost headings sit at the top level with no entry for the
+/// chapter above them.
+pub fn from_headings(spine: &[String], outlines: &[ChapterOutline]) -> Vec<TocEntry> {
+    let mut entries = Vec::new();
+    for (chapter_index, item_id) in spine.iter().enumerate() {
+        let Some(outline) = outlines.iter().find(|o| o.item_id == item_id) else {
+            continue;
+        };
+        let key_prefix = chapter_index.to_string();
+        if outline.headings.is_empty() {
+            entries.push(TocEntry {
+                key: key_prefix,
+                label: item_id.clone(),
+                href: outline.href.to_string(),
+                target_id: Some(item_id.clone()),
+                level: 1,
+                children: Vec::new(),
+            });
+        } else {
+            entries.extend(nest_headings(
+                outline.headings,
+                outline.href,
+                item_id,
+                &key_prefix,
+            ));
+        }
+    }
+    entries
+}
+
+fn nest_headings(
+    headings: &[(u8, String)],
+    href: &str,
+    target_id: &str,
+    key_prefix: &str,
+) -> Vec<TocEntry> {
+    let mut entries = Vec::new();
+    let mut i = 0;
+    let mut ordinal = 0;
+    while i < headings.len() {
+        let level = headings[i].0;
+        let mut j = i + 1;
+        while j < headings.len() && headings[j].0 > level {
+            j += 1;
+        }
+        let key = format!("{key_prefix}.{ordinal}");
+        let children = nest_headings(&headings[i + 1..j], href, target_id, &key);
+        entries.push(TocEntry {
+            key,
+            label: headings[i].1.clone(),
+            href: href.to_string(),
+            target_id: Some(target_id.to_string()),
+            level,
+            children,
+        });
+        ordinal += 1;
+        i = j;
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chapter_with_no_headings_becomes_a_leaf_entry_labeled_by_id() {
+        let spine = vec!["ch1".to_string()];
+        let outlines = vec![ChapterOutline {
+            item_id: "ch1",
+            href: "ch1.xhtml",
+            headings: &[],
+        }];
+        let toc = from_headings(&spine, &outlines);
+        assert_eq!(toc.len(), 1);
+        assert_eq!(toc[0].label, "ch1");
+        assert!(toc[0].children.is_empty());
+        assert_eq!(toc[0].target_id.as_deref(), Some("ch1"));
+    }
+
+    #[test]
+    fn headings_nest_by_level_under_their_chapter() {
+        let spine = vec!["ch1".to_string()];
+        let headings = vec![
+            (1, "Chapter One".to_string()),
+            (2, "Section A".to_string()),
+            (2, "Section B".to_string()),
+            (1, "Chapter Two".to_string()),
+        ];
+        let outlines = vec![ChapterOutline {
+            item_id: "ch1",
+            href: "ch1.xhtml",
+            headings: &headings,
+        }];
+        let toc = from_headings(&spine, &outlines);
+
+        assert_eq!(toc.len(), 2);
+        assert_eq!(toc[0].label, "Chapter One");
+        assert_eq!(toc[0].children.len(), 2);
+        assert_eq!(toc[0].children[0].label, "Section A");
+        assert_eq!(toc[0].children[1].label, "Section B");
+        assert_eq!(toc[1].label, "Chapter Two");
+        assert!(toc[1].children.is_empty());
+    }
+
+    #[test]
+    fn chapters_missing_from_outlines_are_skipped() {
+        let spine = vec!["ch1".to_string(), "ch2".to_string()];
+        let outlines = vec![ChapterOutline {
+            item_id: "ch1",
+            href: "ch1.xhtml",
+            headings: &[],
+        }];
+        let toc = from_headings(&spine, &outlines);
+        assert_eq!(toc.len(), 1);
+        assert_eq!(toc[0].label, "ch1");
+    }
+}
@@ -1,27 +1,119 @@
 use iced::{
-    executor,
+    executor, theme,
     widget::{button, column, row, scrollable, text, text_input, Container},
-    Application, Command, Element, Length, Settings, Theme,
+    Application, Command, Element, Length, Theme,
 };
 use rfd::AsyncFileDialog;
+use std::collections::HashSet;
 
-use crate::epub_handler::EpubHandler;
+use crate::db::Database;
+use crate::epub_handler::{Epub, EpubHandler};
+use crate::export_template::{self, ChapterBindings};
+use crate::text_extract::{self, Heading};
+use crate::toc::TocEntry;
 
 pub struct EpubEditor {
-    epub_handler: EpubHandler,
-    current_content: String,
+    epub: Epub,
+    current_item_id: Option<String>,
     edit_content: String,
+    db: Option<Database>,
+    search_query: String,
+    search_results: Vec<(String, String)>,
+    export_template: String,
+    export_preview: String,
+    collapsed_toc_nodes: HashSet<String>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     OpenEpub,
-    EpubLoaded(Result<String, String>),
+    EpubLoaded(Result<Epub, String>),
+    SelectItem(String),
     EditContent(String),
     SaveContent,
+    ExportEpub,
+    ExportChapter,
+    EpubExported(Result<(), String>),
+    Search(String),
+    UpdateExportTemplate(String),
+    ToggleTocNode(String),
     Test,
 }
 
+fn heading_outline(headings: &[Heading]) -> Element<Message> {
+    headings
+        .iter()
+        .fold(column![].spacing(2), |column, heading| {
+            let indent = "  ".repeat(heading.level.saturating_sub(1) as usize);
+            column.push(text(format!("{indent}{}", heading.text)).size(14))
+        })
+        .into()
+}
+
+/// Renders a TOC tree indented by level, with a running `number_prefix` per
+/// level (`"1"`, `"1.1"`, `"1.2"`, ...) and a +/- toggle on any node that has
+/// children.
+fn render_toc(
+    entries: &[TocEntry],
+    collapsed: &HashSet<String>,
+    current_item_id: &Option<String>,
+    number_prefix: &str,
+) -> Element<Message> {
+    entries
+        .iter()
+        .enumerate()
+        .fold(column![].spacing(2), |column, (i, entry)| {
+            let number = if number_prefix.is_empty() {
+                (i + 1).to_string()
+            } else {
+                format!("{number_prefix}.{}", i + 1)
+            };
+            let indent = "  ".repeat(entry.level.saturating_sub(1) as usize);
+            let is_collapsed = collapsed.contains(&entry.key);
+            let toggle = if entry.children.is_empty() {
+                "  "
+            } else if is_collapsed {
+                "+ "
+            } else {
+                "- "
+            };
+            let is_current = entry.target_id.is_some() && entry.target_id == *current_item_id;
+
+            let label_button = button(text(format!("{indent}{number} {}", entry.label)).size(14))
+                .on_press(match &entry.target_id {
+                    Some(id) => Message::SelectItem(id.clone()),
+                    None => Message::ToggleTocNode(entry.key.clone()),
+                })
+                .style(if is_current {
+                    theme::Button::Primary
+                } else {
+                    theme::Button::Secondary
+                });
+
+            let mut node_row = row![].spacing(2);
+            if !entry.children.is_empty() {
+                node_row = node_row.push(
+                    button(text(toggle).size(14))
+                        .on_press(Message::ToggleTocNode(entry.key.clone()))
+                        .style(theme::Button::Text),
+                );
+            }
+            node_row = node_row.push(label_button);
+
+            let mut node_column = column![node_row];
+            if !entry.children.is_empty() && !is_collapsed {
+                node_column = node_column.push(render_toc(
+                    &entry.children,
+                    collapsed,
+                    current_item_id,
+                    &number,
+                ));
+            }
+            column.push(node_column)
+        })
+        .into()
+}
+
 impl Application for EpubEditor {
     type Message = Message;
     type Theme = Theme;
@@ -29,18 +121,31 @@ impl Application for EpubEditor {
     type Flags = ();
 
     fn new(_flags: ()) -> (Self, Command<Message>) {
+        let db = Database::new()
+            .map_err(|e| println!("Error opening search index: {}", e))
+            .ok();
         (
             Self {
-                epub_handler: EpubHandler::new(),
-                current_content: String::new(),
+                epub: Epub::new(),
+                current_item_id: None,
                 edit_content: String::new(),
+                db,
+                search_query: String::new(),
+                search_results: Vec::new(),
+                export_template: String::new(),
+                export_preview: String::new(),
+                collapsed_toc_nodes: HashSet::new(),
             },
             Command::none(),
         )
     }
 
     fn title(&self) -> String {
-        String::from("EPUB Editor")
+        if self.epub.metadata.title.is_empty() {
+            String::from("EPUB Editor")
+        } else {
+            format!("EPUB Editor - {}", self.epub.metadata.title)
+        }
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
@@ -62,9 +167,18 @@ impl Application for EpubEditor {
             ),
             Message::EpubLoaded(result) => {
                 match result {
-                    Ok(content) => {
-                        self.current_content = content.clone();
-                        self.edit_content = content;
+                    Ok(epub) => {
+                        self.epub = epub;
+                        self.current_item_id = self.epub.spine.first().cloned();
+                        self.edit_content = self
+                            .current_item_id
+                            .as_ref()
+                            .and_then(|id| self.epub.manifest.get(id))
+                            .map(|item| item.plain_text.clone())
+                            .unwrap_or_default();
+                        self.search_results.clear();
+                        self.collapsed_toc_nodes.clear();
+                        self.index_epub();
                     }
                     Err(e) => {
                         println!("Error loading EPUB: {}", e);
@@ -72,17 +186,99 @@ impl Application for EpubEditor {
                 }
                 Command::none()
             }
+            Message::SelectItem(id) => {
+                if let Some(item) = self.epub.manifest.get(&id) {
+                    self.edit_content = item.plain_text.clone();
+                }
+                self.current_item_id = Some(id);
+                Command::none()
+            }
             Message::EditContent(content) => {
                 self.edit_content = content;
                 Command::none()
             }
             Message::SaveContent => {
-                self.current_content = self.edit_content.clone();
-                // TODO: Implement actual saving logic
+                if let Some(id) = &self.current_item_id {
+                    if let Some(item) = self.epub.manifest.get_mut(id) {
+                        item.content = text_extract::reserialize(&self.edit_content);
+                        item.data = item.content.clone().into_bytes();
+                        let extracted = text_extract::extract(&item.content);
+                        item.plain_text = extracted.text;
+                        item.outline = extracted.headings;
+                    }
+                }
+                Command::none()
+            }
+            Message::ExportEpub => {
+                let epub = self.epub.clone();
+                let file_name = self.resolve_book_export_filename();
+                Command::perform(
+                    async move {
+                        let handle = AsyncFileDialog::new()
+                            .add_filter("EPUB", &["epub"])
+                            .set_file_name(&file_name)
+                            .save_file()
+                            .await
+                            .ok_or_else(|| "No destination selected".to_string())?;
+                        EpubHandler::write_epub(&epub, &handle.path().to_owned())
+                    },
+                    Message::EpubExported,
+                )
+            }
+            Message::ExportChapter => {
+                let Some(item) = self
+                    .current_item_id
+                    .as_ref()
+                    .and_then(|id| self.epub.manifest.get(id))
+                    .cloned()
+                else {
+                    return Command::none();
+                };
+                let file_name = self.resolve_export_filename();
+                Command::perform(
+                    async move {
+                        let handle = AsyncFileDialog::new()
+                            .set_file_name(&file_name)
+                            .save_file()
+                            .await
+                            .ok_or_else(|| "No destination selected".to_string())?;
+                        EpubHandler::write_chapter(&item, &handle.path().to_owned())
+                    },
+                    Message::EpubExported,
+                )
+            }
+            Message::EpubExported(result) => {
+                if let Err(e) = result {
+                    println!("Error exporting EPUB: {}", e);
+                }
+                Command::none()
+            }
+            Message::Search(query) => {
+                self.search_query = query;
+                self.search_results = match (&self.db, &self.epub.source_path, self.search_query.as_str()) {
+                    (Some(db), Some(source_path), q) if !q.is_empty() => db
+                        .search(&source_path.to_string_lossy(), q)
+                        .map_err(|e| println!("Error searching: {}", e))
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|hit| (hit.item_id, hit.snippet))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                Command::none()
+            }
+            Message::UpdateExportTemplate(template) => {
+                self.export_template = template;
+                Command::none()
+            }
+            Message::ToggleTocNode(key) => {
+                if !self.collapsed_toc_nodes.remove(&key) {
+                    self.collapsed_toc_nodes.insert(key);
+                }
                 Command::none()
             }
             Message::Test => {
-                println!("[INFO] test");
+                self.export_preview = self.resolve_export_filename();
                 Command::none()
             }
         }
@@ -90,20 +286,76 @@ impl Application for EpubEditor {
 
     fn view(&self) -> Element<Message> {
         let open_button = button("Open EPUB").on_press(Message::OpenEpub);
-        let test = button("[TEST]").on_press(Message::Test);
+        let test = button("Test").on_press(Message::Test);
         let save_button = button("Save Changes").on_press(Message::SaveContent);
+        let export_button = button("Export EPUB").on_press(Message::ExportEpub);
+        let export_chapter_button = button("Export Chapter").on_press(Message::ExportChapter);
+
+        let export_template_input = text_input(
+            export_template::DEFAULT_TEMPLATE,
+            &self.export_template,
+        )
+        .on_input(Message::UpdateExportTemplate)
+        .padding(10)
+        .size(16);
+
+        let toc_view = render_toc(
+            &self.epub.toc,
+            &self.collapsed_toc_nodes,
+            &self.current_item_id,
+            "",
+        );
 
         let editor = text_input("Edit EPUB content", &self.edit_content)
             .on_input(Message::EditContent)
             .padding(10)
             .size(20);
 
+        let outline = self
+            .current_item_id
+            .as_ref()
+            .and_then(|id| self.epub.manifest.get(id))
+            .map(|item| heading_outline(&item.outline))
+            .unwrap_or_else(|| column![].into());
+
+        let search_box = text_input("Search chapters", &self.search_query)
+            .on_input(Message::Search)
+            .padding(10)
+            .size(16);
+
+        let search_results =
+            self.search_results
+                .iter()
+                .fold(column![].spacing(5), |column, (id, snippet)| {
+                    column.push(
+                        button(text(format!("{id}: {snippet}")).size(14))
+                            .on_press(Message::SelectItem(id.clone()))
+                            .style(theme::Button::Text),
+                    )
+                });
+
         let content = column![
             text("EPUB Editor").size(30),
-            row![open_button, save_button, test].spacing(10),
-            scrollable(text(&self.current_content).size(16).width(Length::Fill))
-                .height(Length::FillPortion(1)),
-            editor
+            row![open_button, save_button, export_button, export_chapter_button, test].spacing(10),
+            row![
+                text("Export filename:"),
+                export_template_input,
+                text(&self.export_preview).size(14),
+            ]
+            .spacing(10),
+            search_box,
+            scrollable(search_results).height(Length::Shrink),
+            row![
+                scrollable(column![text("Table of Contents:"), toc_view])
+                    .width(Length::FillPortion(1))
+                    .height(Length::Fill),
+                column![text("Content Editor:"), editor].width(Length::FillPortion(3)),
+                scrollable(column![text("Outline:"), outline])
+                    .width(Length::FillPortion(1))
+                    .height(Length::Fill),
+            ]
+            .spacing(20)
+            .height(Length::Fill),
         ]
         .spacing(20)
         .padding(20)
@@ -119,7 +371,68 @@ impl Application for EpubEditor {
     }
 }
 
-#[tokio::main]
-async fn main() -> iced::Result {
-    EpubEditor::run(Settings::default())
+impl EpubEditor {
+    /// Indexes the currently loaded book's chapters for full-text search,
+    /// keyed by its source path so reopening an unchanged file is a no-op.
+    fn index_epub(&self) {
+        let (Some(db), Some(source_path)) = (&self.db, &self.epub.source_path) else {
+            return;
+        };
+        let mtime = std::fs::metadata(source_path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+
+        let chapters: Vec<(String, String)> = self
+            .epub
+            .spine
+            .iter()
+            .filter_map(|id| self.epub.manifest.get(id))
+            .map(|item| (item.id.clone(), item.plain_text.clone()))
+            .collect();
+
+        if let Err(e) = db.index_book(&source_path.to_string_lossy(), mtime, &chapters) {
+            println!("Error indexing EPUB: {}", e);
+        }
+    }
+
+    /// Resolves `export_template` against the current metadata and selected
+    /// chapter, for per-chapter export (and the "Test" preview). Falls back
+    /// to [`export_template::DEFAULT_TEMPLATE`].
+    fn resolve_export_filename(&self) -> String {
+        let index = self
+            .current_item_id
+            .as_ref()
+            .and_then(|id| self.epub.spine.iter().position(|spine_id| spine_id == id))
+            .map(|pos| pos + 1)
+            .unwrap_or_default();
+        let chapter_title = self
+            .current_item_id
+            .as_ref()
+            .and_then(|id| self.epub.manifest.get(id))
+            .map(|item| item.outline.first().map(|h| h.text.as_str()).unwrap_or(&item.id))
+            .unwrap_or_default();
+
+        export_template::resolve(
+            &self.export_template,
+            &self.epub.metadata,
+            &ChapterBindings {
+                index,
+                chapter_title,
+            },
+        )
+    }
+
+    /// Resolves `export_template` against the current metadata for a
+    /// whole-book export, where there's no single selected chapter to bind
+    /// `{index}`/`{chapterTitle}` to.
+    fn resolve_book_export_filename(&self) -> String {
+        export_template::resolve(
+            &self.export_template,
+            &self.epub.metadata,
+            &ChapterBindings::default(),
+        )
+    }
 }